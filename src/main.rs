@@ -6,17 +6,26 @@
  * https://mozilla.org/MPL/2.0/.
  */
 
+mod mp4;
 mod pgs;
+mod palette;
+mod preview;
+mod raster;
 mod rgb;
 
 use pgs::{
+    Crop,
     Seg,
     SegBody,
     read::{ReadSegExt, SegReadError},
     write::WriteSegExt,
 };
+use palette::{dim, parse_hex_color, pc_to_tv_range, recolor, tv_to_pc_range};
+use preview::{render_ascii, render_sixel, Placement};
+use raster::{decode_rle, encode_rle, resample_nearest};
 use std::{
     collections::HashMap,
+    env,
     fs::File,
     io::{stdin, stdout, BufReader, BufWriter, ErrorKind, Read, Write},
 };
@@ -34,6 +43,52 @@ struct Size {
     height: u16,
 }
 
+#[derive(Clone, Copy)]
+struct Clip {
+    skip_x: u16,
+    skip_y: u16,
+    width: u16,
+    height: u16,
+}
+
+struct PendingFrame {
+    comp_num: u16,
+    pal_id: u8,
+    canvas_size: Size,
+    objects: Vec<(u16, u16, u16)>,
+}
+
+fn render_preview_frame(
+    frame: &PendingFrame,
+    palettes: &HashMap<u8, HashMap<u8, (u8, u8, u8)>>,
+    obj_rasters: &HashMap<ObjHandle, (u16, u16, Vec<u8>)>,
+    preview_sixel: bool,
+) {
+
+    let empty_palette = HashMap::new();
+    let palette = palettes.get(&frame.pal_id).unwrap_or(&empty_palette);
+    let placements = frame.objects.iter().filter_map(|&(obj_id, x, y)| {
+        obj_rasters.get(&ObjHandle { comp_num: frame.comp_num, obj_id }).map(
+            |(width, height, raster)| Placement {
+                x,
+                y,
+                width: *width,
+                height: *height,
+                raster: raster.clone(),
+            }
+        )
+    }).collect::<Vec<_>>();
+    let canvas = preview::composite(
+        frame.canvas_size.width, frame.canvas_size.height, &placements, palette,
+    );
+
+    if preview_sixel {
+        println!("{}", render_sixel(&canvas, frame.canvas_size.width, frame.canvas_size.height));
+    } else {
+        println!("{}", render_ascii(&canvas, frame.canvas_size.width, frame.canvas_size.height));
+    }
+}
+
 fn main() {
 
     let matches = App::new("PGSMod")
@@ -45,12 +100,12 @@ fn main() {
             .value_name("PIXELS")
             .help("Width to crop each subtitle frame to")
             .takes_value(true)
-            .required(true)
+            .required(false)
             .validator(|value| {
-                if value.parse::<usize>().is_ok() {
+                if value.parse::<u16>().is_ok() {
                     Ok(())
                 } else {
-                    Err("must be an unsigned integer".to_string())
+                    Err("must be an unsigned 16-bit integer".to_string())
                 }
             })
         )
@@ -60,15 +115,130 @@ fn main() {
             .value_name("PIXELS")
             .help("Height to crop each subtitle frame to")
             .takes_value(true)
-            .required(true)
+            .required(false)
             .validator(|value| {
-                if value.parse::<usize>().is_ok() {
+                if value.parse::<u16>().is_ok() {
                     Ok(())
                 } else {
-                    Err("must be an unsigned integer".to_string())
+                    Err("must be an unsigned 16-bit integer".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("pad-width")
+            .long("pad-width")
+            .value_name("PIXELS")
+            .help("Width to pad each subtitle frame out to")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if value.parse::<u16>().is_ok() {
+                    Ok(())
+                } else {
+                    Err("must be an unsigned 16-bit integer".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("pad-height")
+            .long("pad-height")
+            .value_name("PIXELS")
+            .help("Height to pad each subtitle frame out to")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if value.parse::<u16>().is_ok() {
+                    Ok(())
+                } else {
+                    Err("must be an unsigned 16-bit integer".to_string())
                 }
             })
         )
+        .arg(Arg::with_name("scale-width")
+            .long("scale-width")
+            .value_name("PIXELS")
+            .help("Width to resample each subtitle bitmap to, for a resized main video")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if value.parse::<u16>().is_ok() {
+                    Ok(())
+                } else {
+                    Err("must be an unsigned 16-bit integer".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("scale-height")
+            .long("scale-height")
+            .value_name("PIXELS")
+            .help("Height to resample each subtitle bitmap to, for a resized main video")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if value.parse::<u16>().is_ok() {
+                    Ok(())
+                } else {
+                    Err("must be an unsigned 16-bit integer".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("tv-to-pc-range")
+            .long("tv-to-pc-range")
+            .help("Convert the subtitle palette from limited (TV) range to full (PC) range")
+            .takes_value(false)
+            .required(false)
+            .conflicts_with("pc-to-tv-range")
+        )
+        .arg(Arg::with_name("pc-to-tv-range")
+            .long("pc-to-tv-range")
+            .help("Convert the subtitle palette from full (PC) range to limited (TV) range")
+            .takes_value(false)
+            .required(false)
+            .conflicts_with("tv-to-pc-range")
+        )
+        .arg(Arg::with_name("recolor")
+            .long("recolor")
+            .value_name("RRGGBB")
+            .help("Tint the subtitle palette by multiplying it against a hex RGB color")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if parse_hex_color(&value).is_some() {
+                    Ok(())
+                } else {
+                    Err("must be a hex RGB color, e.g. ffcc00".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("dim")
+            .long("dim")
+            .value_name("FACTOR")
+            .help("Scale the subtitle palette's brightness by this factor (e.g. 0.8)")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                match value.parse::<f64>() {
+                    Ok(factor) if factor >= 0.0 => Ok(()),
+                    _ => Err("must be a non-negative decimal number".to_string()),
+                }
+            })
+        )
+        .arg(Arg::with_name("mp4")
+            .long("mp4")
+            .help("Mux the output into an MP4 container (moov before mdat) instead of a raw .sup")
+            .takes_value(false)
+            .required(false)
+        )
+        .arg(Arg::with_name("preview")
+            .long("preview")
+            .help("Render each composited display set to the terminal for QA")
+            .takes_value(false)
+            .required(false)
+        )
+        .arg(Arg::with_name("preview-ascii")
+            .long("preview-ascii")
+            .help("Force the ASCII preview renderer instead of sixel")
+            .takes_value(false)
+            .required(false)
+        )
         .arg(Arg::with_name("margin")
             .long("margin")
             .short("m")
@@ -99,11 +269,51 @@ fn main() {
         )
         .after_help("This utility will crop PGS subtitles found in Blu-ray discs so that they \
             can match any cropping that has been done to the main video stream, thereby \
-            preventing the subtitles from appearing squished or distorted by the player.")
+            preventing the subtitles from appearing squished or distorted by the player. If \
+            the main video instead gained letterboxing bars, --pad-width and --pad-height can \
+            be used to expand the subtitle canvas instead. If the main video was resized, \
+            --scale-width and --scale-height can be used to resample the subtitle bitmaps to \
+            match.")
         .get_matches();
-    let crop_width = matches.value_of("crop-width").unwrap().parse::<u16>().unwrap();
-    let crop_height = matches.value_of("crop-height").unwrap().parse::<u16>().unwrap();
+    let crop_width = matches.value_of("crop-width").map(|value| value.parse::<u16>().unwrap());
+    let crop_height = matches.value_of("crop-height").map(|value| value.parse::<u16>().unwrap());
+    let pad_width = matches.value_of("pad-width").map(|value| value.parse::<u16>().unwrap());
+    let pad_height = matches.value_of("pad-height").map(|value| value.parse::<u16>().unwrap());
     let margin = matches.value_of("margin").unwrap().parse::<u16>().unwrap();
+    let scale_width = matches.value_of("scale-width").map(|value| value.parse::<u16>().unwrap());
+    let scale_height = matches.value_of("scale-height").map(|value| value.parse::<u16>().unwrap());
+    let tv_to_pc = matches.is_present("tv-to-pc-range");
+    let pc_to_tv = matches.is_present("pc-to-tv-range");
+    let recolor_tint = matches.value_of("recolor").map(|value| parse_hex_color(value).unwrap());
+    let dim_factor = matches.value_of("dim").map(|value| value.parse::<f64>().unwrap());
+    let mp4_mode = matches.is_present("mp4");
+    let preview = matches.is_present("preview");
+    let preview_sixel = preview && !matches.is_present("preview-ascii") && env::var("TERM")
+        .map(|term| term.contains("xterm") || term.contains("mlterm") || term.contains("kitty"))
+        .unwrap_or(false);
+
+    if scale_width.is_some() != scale_height.is_some() {
+        panic!("--scale-width and --scale-height must be specified together.")
+    }
+    if crop_width.is_some() != crop_height.is_some() {
+        panic!("--crop-width and --crop-height must be specified together.")
+    }
+    if pad_width.is_some() != pad_height.is_some() {
+        panic!("--pad-width and --pad-height must be specified together.")
+    }
+    if crop_width.is_some() && pad_width.is_some() {
+        panic!("--crop-width/--crop-height and --pad-width/--pad-height are mutually exclusive.")
+    }
+    if crop_width.is_none() && pad_width.is_none() {
+        panic!("Either --crop-width/--crop-height or --pad-width/--pad-height must be specified.")
+    }
+
+    let crop_mode = crop_width.is_some();
+    let (target_width, target_height) = if crop_mode {
+        (crop_width.unwrap(), crop_height.unwrap())
+    } else {
+        (pad_width.unwrap(), pad_height.unwrap())
+    };
     let input_value = matches.value_of("input").unwrap();
     let (mut stdin_read, mut file_read);
     let mut input = BufReader::<&mut dyn Read>::new(
@@ -178,8 +388,106 @@ fn main() {
         }
     }
 
+    if let (Some(scale_width), Some(scale_height)) = (scale_width, scale_height) {
+
+        eprintln!("Resampling subtitle bitmaps for target scale...");
+
+        let mut comp_num = 0;
+        let mut orig_size = Size { width: 0, height: 0 };
+
+        for seg in segs.iter_mut() {
+            match &mut seg.body {
+                SegBody::PresComp(pcs) => {
+                    comp_num = pcs.comp_num;
+                    orig_size = Size { width: pcs.width, height: pcs.height };
+                    let scale_x = scale_width as f64 / orig_size.width as f64;
+                    let scale_y = scale_height as f64 / orig_size.height as f64;
+                    for comp_obj in pcs.comp_objs.iter_mut() {
+                        comp_obj.x = (comp_obj.x as f64 * scale_x).round() as u16;
+                        comp_obj.y = (comp_obj.y as f64 * scale_y).round() as u16;
+                        if let Some(crop) = &mut comp_obj.crop {
+                            crop.x = (crop.x as f64 * scale_x).round() as u16;
+                            crop.y = (crop.y as f64 * scale_y).round() as u16;
+                            crop.width = (crop.width as f64 * scale_x).round() as u16;
+                            crop.height = (crop.height as f64 * scale_y).round() as u16;
+                        }
+                    }
+                    pcs.width = scale_width;
+                    pcs.height = scale_height;
+                }
+                SegBody::WinDef(wds) => {
+                    let scale_x = scale_width as f64 / orig_size.width as f64;
+                    let scale_y = scale_height as f64 / orig_size.height as f64;
+                    for wd in wds.iter_mut() {
+                        wd.x = (wd.x as f64 * scale_x).round() as u16;
+                        wd.y = (wd.y as f64 * scale_y).round() as u16;
+                        wd.width = (wd.width as f64 * scale_x).round() as u16;
+                        wd.height = (wd.height as f64 * scale_y).round() as u16;
+                    }
+                }
+                SegBody::ObjDef(ods) => {
+                    let scale_x = scale_width as f64 / orig_size.width as f64;
+                    let scale_y = scale_height as f64 / orig_size.height as f64;
+                    let new_width = (ods.width as f64 * scale_x).round() as u16;
+                    let new_height = (ods.height as f64 * scale_y).round() as u16;
+                    let raster = decode_rle(&ods.data, ods.width, ods.height);
+                    let resampled = resample_nearest(
+                        &raster, ods.width, ods.height, new_width, new_height,
+                    );
+                    ods.data = encode_rle(&resampled, new_width, new_height);
+                    ods.width = new_width;
+                    ods.height = new_height;
+                    obj_sizes.insert(
+                        ObjHandle { comp_num, obj_id: ods.id },
+                        Size { width: new_width, height: new_height },
+                    );
+                }
+                _ => ()
+            }
+        }
+    }
+
+    if tv_to_pc || pc_to_tv || recolor_tint.is_some() || dim_factor.is_some() {
+
+        eprintln!("Applying palette transforms...");
+
+        for seg in segs.iter_mut() {
+            if let SegBody::PalDef(pds) = &mut seg.body {
+                for entry in pds.entries.iter_mut() {
+                    // recolor/dim operate on ycbcr_to_rgb's native limited-range assumption, so
+                    // they must run before any range conversion is applied to the entry.
+                    if recolor_tint.is_some() || dim_factor.is_some() {
+                        let mut color = rgb::ycbcr_to_rgb(entry.y, entry.cb, entry.cr);
+                        if let Some(tint) = recolor_tint {
+                            color = recolor(color, tint);
+                        }
+                        if let Some(factor) = dim_factor {
+                            color = dim(color, factor);
+                        }
+                        let (y, cb, cr) = rgb::rgb_to_ycbcr(color.0, color.1, color.2);
+                        entry.y = y;
+                        entry.cb = cb;
+                        entry.cr = cr;
+                    }
+                    if tv_to_pc {
+                        let (y, cb, cr) = tv_to_pc_range(entry.y, entry.cb, entry.cr);
+                        entry.y = y;
+                        entry.cb = cb;
+                        entry.cr = cr;
+                    } else if pc_to_tv {
+                        let (y, cb, cr) = pc_to_tv_range(entry.y, entry.cb, entry.cr);
+                        entry.y = y;
+                        entry.cb = cb;
+                        entry.cr = cr;
+                    }
+                }
+            }
+        }
+    }
+
     let mut screen_sizes = Vec::<Size>::new();
     let mut screen_full_size = Size { width: 0, height: 0 };
+    let mut clips = HashMap::<ObjHandle, Clip>::new();
 
     eprintln!("Performing modifications...");
 
@@ -196,102 +504,338 @@ fn main() {
                     screen_sizes.push(screen_full_size);
                 }
                 for comp_obj in pcs.comp_objs.iter_mut() {
-                    let obj_size = obj_sizes.get(
+                    let obj_size = *obj_sizes.get(
                         &ObjHandle { comp_num, obj_id: comp_obj.obj_id }
                     ).expect("Could not find object size.");
-                    comp_obj.x = cropped_object_offset(
-                        screen_full_size.width,
-                        crop_width,
-                        obj_size.width,
-                        comp_obj.x,
-                        margin,
-                    );
-                    comp_obj.y = cropped_object_offset(
-                        screen_full_size.height,
-                        crop_height,
-                        obj_size.height,
-                        comp_obj.y,
-                        margin,
-                    );
-                    match &mut comp_obj.crop {
-                        Some(crop) => {
-                            crop.x = cropped_object_offset(
-                                screen_full_size.width,
-                                crop_width,
-                                crop.width,
-                                crop.x,
-                                margin,
-                            );
-                            crop.y = cropped_object_offset(
-                                screen_full_size.height,
-                                crop_height,
-                                crop.height,
-                                crop.y,
-                                margin,
+                    if crop_mode {
+                        let (x, width, skip_x) = clipped_axis(
+                            screen_full_size.width, target_width, obj_size.width, comp_obj.x, margin,
+                        );
+                        let (y, height, skip_y) = clipped_axis(
+                            screen_full_size.height, target_height, obj_size.height, comp_obj.y, margin,
+                        );
+                        comp_obj.x = x;
+                        comp_obj.y = y;
+                        match &mut comp_obj.crop {
+                            Some(crop) => {
+                                let (cx, cwidth, _) = clipped_axis(
+                                    screen_full_size.width, target_width, crop.width, crop.x, margin,
+                                );
+                                let (cy, cheight, _) = clipped_axis(
+                                    screen_full_size.height, target_height, crop.height, crop.y, margin,
+                                );
+                                crop.x = cx;
+                                crop.y = cy;
+                                crop.width = cwidth;
+                                crop.height = cheight;
+                            }
+                            None => {
+                                ()
+                            }
+                        }
+                        if width != obj_size.width || height != obj_size.height {
+                            match &mut comp_obj.crop {
+                                Some(crop) => {
+                                    let start_x = crop.x.max(skip_x);
+                                    let start_y = crop.y.max(skip_y);
+                                    let end_x = (crop.x + crop.width).min(skip_x + width);
+                                    let end_y = (crop.y + crop.height).min(skip_y + height);
+                                    crop.x = start_x.saturating_sub(skip_x);
+                                    crop.y = start_y.saturating_sub(skip_y);
+                                    crop.width = end_x.saturating_sub(start_x);
+                                    crop.height = end_y.saturating_sub(start_y);
+                                }
+                                None => {
+                                    comp_obj.crop = Some(Crop { x: 0, y: 0, width, height });
+                                }
+                            }
+                            clips.insert(
+                                ObjHandle { comp_num, obj_id: comp_obj.obj_id },
+                                Clip { skip_x, skip_y, width, height },
                             );
                         }
-                        None => {
-                            ()
+                    } else {
+                        comp_obj.x = padded_object_offset(
+                            screen_full_size.width,
+                            target_width,
+                            obj_size.width,
+                            comp_obj.x,
+                            margin,
+                        );
+                        comp_obj.y = padded_object_offset(
+                            screen_full_size.height,
+                            target_height,
+                            obj_size.height,
+                            comp_obj.y,
+                            margin,
+                        );
+                        match &mut comp_obj.crop {
+                            Some(crop) => {
+                                crop.x = padded_object_offset(
+                                    screen_full_size.width,
+                                    target_width,
+                                    crop.width,
+                                    crop.x,
+                                    margin,
+                                );
+                                crop.y = padded_object_offset(
+                                    screen_full_size.height,
+                                    target_height,
+                                    crop.height,
+                                    crop.y,
+                                    margin,
+                                );
+                            }
+                            None => {
+                                ()
+                            }
                         }
                     }
                 }
-                pcs.width = crop_width;
-                pcs.height = crop_height;
+                pcs.width = target_width;
+                pcs.height = target_height;
             }
             SegBody::WinDef(wds) => {
                 for wd in wds.iter_mut() {
-                    wd.x = cropped_object_offset(
-                        screen_full_size.width,
-                        crop_width,
-                        wd.width,
-                        wd.x,
-                        margin,
-                    );
-                    wd.y = cropped_object_offset(
-                        screen_full_size.height,
-                        crop_height,
-                        wd.height,
-                        wd.y,
-                        margin,
-                    );
+                    if crop_mode {
+                        let (x, width, _) = clipped_axis(
+                            screen_full_size.width, target_width, wd.width, wd.x, margin,
+                        );
+                        let (y, height, _) = clipped_axis(
+                            screen_full_size.height, target_height, wd.height, wd.y, margin,
+                        );
+                        wd.x = x;
+                        wd.y = y;
+                        wd.width = width;
+                        wd.height = height;
+                    } else {
+                        wd.x = padded_object_offset(
+                            screen_full_size.width,
+                            target_width,
+                            wd.width,
+                            wd.x,
+                            margin,
+                        );
+                        wd.y = padded_object_offset(
+                            screen_full_size.height,
+                            target_height,
+                            wd.height,
+                            wd.y,
+                            margin,
+                        );
+                    }
+                }
+            }
+            SegBody::ObjDef(ods) => {
+                if let Some(clip) = clips.get(&ObjHandle { comp_num, obj_id: ods.id }) {
+                    let raster = decode_rle(&ods.data, ods.width, ods.height);
+                    let mut trimmed = vec![0u8; clip.width as usize * clip.height as usize];
+                    for row in 0..clip.height as usize {
+                        let src_row = row + clip.skip_y as usize;
+                        for col in 0..clip.width as usize {
+                            let src_col = col + clip.skip_x as usize;
+                            trimmed[row * clip.width as usize + col] =
+                                raster[src_row * ods.width as usize + src_col];
+                        }
+                    }
+                    ods.data = encode_rle(&trimmed, clip.width, clip.height);
+                    ods.width = clip.width;
+                    ods.height = clip.height;
                 }
             }
             _ => ()
         }
     }
 
-    eprintln!("Writing modified segments...");
+    if preview {
+
+        eprintln!("Rendering display sets for preview...");
+
+        let mut palettes = HashMap::<u8, HashMap<u8, (u8, u8, u8)>>::new();
+        let mut obj_rasters = HashMap::<ObjHandle, (u16, u16, Vec<u8>)>::new();
+        let mut comp_num = 0;
+        let mut pending: Option<PendingFrame> = None;
+
+        for seg in segs.iter() {
+            match &seg.body {
+                SegBody::PresComp(pcs) => {
+                    if let Some(frame) = pending.take() {
+                        render_preview_frame(&frame, &palettes, &obj_rasters, preview_sixel);
+                    }
+                    comp_num = pcs.comp_num;
+                    pending = Some(PendingFrame {
+                        comp_num,
+                        pal_id: pcs.pal_id,
+                        canvas_size: Size { width: pcs.width, height: pcs.height },
+                        objects: pcs.comp_objs.iter().map(
+                            |comp_obj| (comp_obj.obj_id, comp_obj.x, comp_obj.y)
+                        ).collect(),
+                    });
+                }
+                SegBody::PalDef(pds) => {
+                    let palette = palettes.entry(pds.id).or_insert_with(HashMap::new);
+                    for entry in pds.entries.iter() {
+                        palette.insert(entry.id, rgb::ycbcr_to_rgb(entry.y, entry.cb, entry.cr));
+                    }
+                }
+                SegBody::ObjDef(ods) => {
+                    obj_rasters.insert(
+                        ObjHandle { comp_num, obj_id: ods.id },
+                        (ods.width, ods.height, decode_rle(&ods.data, ods.width, ods.height)),
+                    );
+                }
+                _ => ()
+            }
+        }
+
+        if let Some(frame) = pending.take() {
+            render_preview_frame(&frame, &palettes, &obj_rasters, preview_sixel);
+        }
+    }
+
+    if mp4_mode {
+
+        eprintln!("Muxing segments into MP4 container...");
+
+        let mut samples = Vec::<mp4::Sample>::new();
+        let mut current: Option<mp4::Sample> = None;
+
+        for seg in segs.iter() {
+            if let SegBody::PresComp(_) = &seg.body {
+                if let Some(sample) = current.take() {
+                    samples.push(sample);
+                }
+                current = Some(mp4::Sample { pts: seg.pts, data: Vec::new() });
+            }
+            match current.as_mut() {
+                Some(sample) => {
+                    sample.data.write_seg(seg)
+                        .expect("Could not serialize segment into MP4 sample.");
+                }
+                None => {
+                    panic!("PGS stream did not begin with a presentation composition segment.")
+                }
+            }
+        }
+
+        if let Some(sample) = current.take() {
+            samples.push(sample);
+        }
+
+        let container = mp4::mux(&samples, target_width, target_height, 90_000);
+
+        output.write_all(&container).expect("Could not write MP4 container to output stream.");
+    } else {
+
+        eprintln!("Writing modified segments...");
 
-    for seg in segs {
-        if let Err(err) = output.write_seg(&seg) {
-            panic!("Could not write frame to output stream: {:?}", err)
+        for seg in segs {
+            if let Err(err) = output.write_seg(&seg) {
+                panic!("Could not write frame to output stream: {:?}", err)
+            }
         }
     }
 
     output.flush().expect("Could not flush output stream.");
 }
 
-fn cropped_object_offset(
+fn clipped_axis(
     screen_full_size: u16,
     screen_crop_size: u16,
     object_size: u16,
     object_offset: u16,
     margin: u16,
-) -> u16 {
+) -> (u16, u16, u16) {
+
+    let border = (screen_full_size - screen_crop_size) / 2;
+    let shifted = object_offset as i32 - border as i32;
+    let end = shifted + object_size as i32;
+    let visible_start = shifted.max(0);
+    let visible_end = end.min(screen_crop_size as i32);
 
-    if object_size + 2 * margin > screen_crop_size {
-        eprintln!("WARNING: Object or window cannot fit within new margins.");
-        return 0
+    if visible_end <= visible_start {
+        eprintln!("WARNING: Object falls entirely outside the cropped region; dropping it.");
+        return (margin, 0, 0)
     }
 
-    let new_offset = object_offset - (screen_full_size - screen_crop_size) / 2;
+    let skip = (visible_start - shifted) as u16;
+    let mut new_offset = visible_start as u16;
+    let mut new_size = (visible_end - visible_start) as u16;
+
+    if new_offset < margin {
+        new_offset = margin;
+    }
+    if new_offset + new_size + margin > screen_crop_size {
+        new_size = screen_crop_size - new_offset - margin;
+    }
+
+    (new_offset, new_size, skip)
+}
+
+fn padded_object_offset(
+    screen_full_size: u16,
+    screen_pad_size: u16,
+    object_size: u16,
+    object_offset: u16,
+    margin: u16,
+) -> u16 {
+
+    let new_offset = object_offset + (screen_pad_size - screen_full_size) / 2;
 
     match new_offset {
         o if o < margin =>
             margin,
-        o if o + object_size + margin > screen_crop_size =>
-            screen_crop_size - object_size - margin,
+        o if o + object_size + margin > screen_pad_size =>
+            screen_pad_size - object_size - margin,
         _ =>
             new_offset,
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn clipped_axis_trims_and_reports_skip() {
+        let (offset, size, skip) = clipped_axis(1920, 1600, 200, 100, 30);
+        assert_eq!((offset, size, skip), (30, 140, 60));
+    }
+
+    #[test]
+    fn clipped_axis_drops_object_entirely_outside_crop_region() {
+        let (offset, size, skip) = clipped_axis(1920, 1600, 50, 100, 30);
+        assert_eq!((offset, size, skip), (30, 0, 0));
+    }
+
+    #[test]
+    fn clipped_axis_clamps_offset_up_to_margin() {
+        let (offset, size, skip) = clipped_axis(1920, 1900, 1850, 0, 30);
+        assert_eq!((offset, size, skip), (30, 1840, 10));
+    }
+
+    #[test]
+    fn clipped_axis_shrinks_size_to_respect_far_margin() {
+        let (offset, size, skip) = clipped_axis(1920, 1900, 1870, 5, 30);
+        assert_eq!((offset, size, skip), (30, 1840, 5));
+    }
+
+    #[test]
+    fn padded_object_offset_shifts_by_half_the_added_border() {
+        let offset = padded_object_offset(1280, 1920, 100, 50, 30);
+        assert_eq!(offset, 370);
+    }
+
+    #[test]
+    fn padded_object_offset_clamps_up_to_margin() {
+        let offset = padded_object_offset(1280, 1282, 50, 0, 30);
+        assert_eq!(offset, 30);
+    }
+
+    #[test]
+    fn padded_object_offset_clamps_down_to_respect_far_margin() {
+        let offset = padded_object_offset(1280, 1282, 50, 1210, 30);
+        assert_eq!(offset, 1202);
+    }
+}
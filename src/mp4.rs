@@ -0,0 +1,305 @@
+/*
+ * Copyright 2020 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ */
+
+pub struct Sample {
+    pub pts: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn mux(samples: &[Sample], width: u16, height: u16, timescale: u32) -> Vec<u8> {
+
+    let mut out = Vec::new();
+
+    write_ftyp(&mut out);
+
+    let mdat_header_size = 8u64;
+    let moov_placeholder = out.len();
+
+    write_moov(&mut out, samples, width, height, timescale, 0);
+
+    let mdat_offset = out.len() as u64 + mdat_header_size;
+
+    out.truncate(moov_placeholder);
+    write_moov(&mut out, samples, width, height, timescale, mdat_offset);
+    write_mdat(&mut out, samples);
+
+    out
+}
+
+fn write_box<F: FnOnce(&mut Vec<u8>)>(out: &mut Vec<u8>, fourcc: &[u8; 4], body: F) {
+
+    let start = out.len();
+
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(fourcc);
+    body(out);
+
+    let size = (out.len() - start) as u32;
+
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box<F: FnOnce(&mut Vec<u8>)>(
+    out: &mut Vec<u8>, fourcc: &[u8; 4], version: u8, flags: u32, body: F,
+) {
+    write_box(out, fourcc, |out| {
+        out.push(version);
+        out.extend_from_slice(&flags.to_be_bytes()[1..4]);
+        body(out);
+    });
+}
+
+fn write_ftyp(out: &mut Vec<u8>) {
+    write_box(out, b"ftyp", |out| {
+        out.extend_from_slice(b"isom");
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(b"isom");
+        out.extend_from_slice(b"iso6");
+    });
+}
+
+fn write_mdat(out: &mut Vec<u8>, samples: &[Sample]) {
+    write_box(out, b"mdat", |out| {
+        for sample in samples {
+            out.extend_from_slice(&sample.data);
+        }
+    });
+}
+
+fn write_moov(
+    out: &mut Vec<u8>, samples: &[Sample], width: u16, height: u16, timescale: u32, chunk_offset: u64,
+) {
+    let duration = samples.iter().map(|sample| sample.pts).max().unwrap_or(0);
+
+    write_box(out, b"moov", |out| {
+        write_mvhd(out, duration, timescale);
+        write_trak(out, samples, width, height, timescale, duration, chunk_offset);
+    });
+}
+
+fn write_mvhd(out: &mut Vec<u8>, duration: u32, timescale: u32) {
+    write_full_box(out, b"mvhd", 0, 0, |out| {
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&timescale.to_be_bytes());
+        out.extend_from_slice(&duration.to_be_bytes());
+        out.extend_from_slice(&0x00010000u32.to_be_bytes());
+        out.extend_from_slice(&0x0100u16.to_be_bytes());
+        out.extend_from_slice(&[0u8; 10]);
+        write_identity_matrix(out);
+        out.extend_from_slice(&[0u8; 24]);
+        out.extend_from_slice(&2u32.to_be_bytes());
+    });
+}
+
+fn write_identity_matrix(out: &mut Vec<u8>) {
+    let matrix: [u32; 9] = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000];
+    for value in matrix.iter() {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn write_trak(
+    out: &mut Vec<u8>, samples: &[Sample], width: u16, height: u16, timescale: u32, duration: u32,
+    chunk_offset: u64,
+) {
+    write_box(out, b"trak", |out| {
+        write_tkhd(out, width, height, duration);
+        write_mdia(out, samples, timescale, duration, chunk_offset);
+    });
+}
+
+fn write_tkhd(out: &mut Vec<u8>, width: u16, height: u16, duration: u32) {
+    write_full_box(out, b"tkhd", 0, 7, |out| {
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&1u32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&duration.to_be_bytes());
+        out.extend_from_slice(&[0u8; 8]);
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        write_identity_matrix(out);
+        out.extend_from_slice(&((width as u32) << 16).to_be_bytes());
+        out.extend_from_slice(&((height as u32) << 16).to_be_bytes());
+    });
+}
+
+fn write_mdia(out: &mut Vec<u8>, samples: &[Sample], timescale: u32, duration: u32, chunk_offset: u64) {
+    write_box(out, b"mdia", |out| {
+        write_mdhd(out, timescale, duration);
+        write_hdlr(out);
+        write_minf(out, samples, chunk_offset);
+    });
+}
+
+fn write_mdhd(out: &mut Vec<u8>, timescale: u32, duration: u32) {
+    write_full_box(out, b"mdhd", 0, 0, |out| {
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&timescale.to_be_bytes());
+        out.extend_from_slice(&duration.to_be_bytes());
+        out.extend_from_slice(&0x55C4u16.to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+    });
+}
+
+fn write_hdlr(out: &mut Vec<u8>) {
+    write_full_box(out, b"hdlr", 0, 0, |out| {
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(b"subt");
+        out.extend_from_slice(&[0u8; 12]);
+        out.extend_from_slice(b"PGS Subtitle Handler\0");
+    });
+}
+
+fn write_minf(out: &mut Vec<u8>, samples: &[Sample], chunk_offset: u64) {
+    write_box(out, b"minf", |out| {
+        write_full_box(out, b"nmhd", 0, 0, |_| ());
+        write_dinf(out);
+        write_stbl(out, samples, chunk_offset);
+    });
+}
+
+fn write_dinf(out: &mut Vec<u8>) {
+    write_box(out, b"dinf", |out| {
+        write_full_box(out, b"dref", 0, 0, |out| {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            write_full_box(out, b"url ", 0, 1, |_| ());
+        });
+    });
+}
+
+fn write_stbl(out: &mut Vec<u8>, samples: &[Sample], chunk_offset: u64) {
+    write_box(out, b"stbl", |out| {
+        write_stsd(out);
+        write_stts(out, samples);
+        write_stsc(out, samples);
+        write_stsz(out, samples);
+        write_stco(out, samples, chunk_offset);
+    });
+}
+
+fn write_stsd(out: &mut Vec<u8>) {
+    write_full_box(out, b"stsd", 0, 0, |out| {
+        out.extend_from_slice(&1u32.to_be_bytes());
+        write_box(out, b"pgsh", |out| {
+            out.extend_from_slice(&[0u8; 6]);
+            out.extend_from_slice(&1u16.to_be_bytes());
+        });
+    });
+}
+
+fn write_stts(out: &mut Vec<u8>, samples: &[Sample]) {
+    write_full_box(out, b"stts", 0, 0, |out| {
+        let mut entries = Vec::<(u32, u32)>::new();
+        for window in samples.windows(2) {
+            let delta = window[1].pts.saturating_sub(window[0].pts);
+            match entries.last_mut() {
+                Some((count, last_delta)) if *last_delta == delta => {
+                    *count += 1;
+                }
+                _ => {
+                    entries.push((1, delta));
+                }
+            }
+        }
+        if !samples.is_empty() {
+            entries.push((1, 0));
+        }
+        out.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for (count, delta) in entries {
+            out.extend_from_slice(&count.to_be_bytes());
+            out.extend_from_slice(&delta.to_be_bytes());
+        }
+    });
+}
+
+fn write_stsc(out: &mut Vec<u8>, samples: &[Sample]) {
+    write_full_box(out, b"stsc", 0, 0, |out| {
+        if samples.is_empty() {
+            out.extend_from_slice(&0u32.to_be_bytes());
+        } else {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+            out.extend_from_slice(&1u32.to_be_bytes());
+        }
+    });
+}
+
+fn write_stsz(out: &mut Vec<u8>, samples: &[Sample]) {
+    write_full_box(out, b"stsz", 0, 0, |out| {
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+        for sample in samples {
+            out.extend_from_slice(&(sample.data.len() as u32).to_be_bytes());
+        }
+    });
+}
+
+fn write_stco(out: &mut Vec<u8>, samples: &[Sample], chunk_offset: u64) {
+    write_full_box(out, b"stco", 0, 0, |out| {
+        if samples.is_empty() {
+            out.extend_from_slice(&0u32.to_be_bytes());
+        } else {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(&(chunk_offset as u32).to_be_bytes());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn find_box(data: &[u8], fourcc: &[u8; 4]) -> usize {
+        data.windows(4).position(|window| window == fourcc).expect("box not found") - 4
+    }
+
+    fn read_u32_be(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    #[test]
+    fn stco_offset_points_at_first_sample() {
+
+        let samples = vec![
+            Sample { pts: 0, data: b"AAAA".to_vec() },
+            Sample { pts: 90_000, data: b"BBBBBB".to_vec() },
+        ];
+        let container = mux(&samples, 1920, 1080, 90_000);
+
+        let mdat_start = find_box(&container, b"mdat");
+        let payload_offset = (mdat_start + 8) as u32;
+
+        let stco_start = find_box(&container, b"stco");
+        let entry_count = read_u32_be(&container, stco_start + 12);
+        let chunk_offset = read_u32_be(&container, stco_start + 16);
+
+        assert_eq!(entry_count, 1);
+        assert_eq!(chunk_offset, payload_offset);
+        assert_eq!(&container[payload_offset as usize..payload_offset as usize + 4], b"AAAA");
+    }
+
+    #[test]
+    fn empty_sample_list_produces_empty_tables() {
+
+        let samples: Vec<Sample> = Vec::new();
+        let container = mux(&samples, 1920, 1080, 90_000);
+
+        let stco_start = find_box(&container, b"stco");
+        let stsc_start = find_box(&container, b"stsc");
+
+        assert_eq!(read_u32_be(&container, stco_start + 12), 0);
+        assert_eq!(read_u32_be(&container, stsc_start + 12), 0);
+    }
+}
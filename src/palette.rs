@@ -0,0 +1,98 @@
+/*
+ * Copyright 2020 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ */
+
+pub fn tv_to_pc_range(y: u8, cb: u8, cr: u8) -> (u8, u8, u8) {
+    (
+        ((y as f64 - 16.0) * 255.0 / 219.0).round().clamp(0.0, 255.0) as u8,
+        ((cb as f64 - 128.0) * 255.0 / 224.0 + 128.0).round().clamp(0.0, 255.0) as u8,
+        ((cr as f64 - 128.0) * 255.0 / 224.0 + 128.0).round().clamp(0.0, 255.0) as u8,
+    )
+}
+
+pub fn pc_to_tv_range(y: u8, cb: u8, cr: u8) -> (u8, u8, u8) {
+    (
+        (y as f64 * 219.0 / 255.0 + 16.0).round().clamp(0.0, 255.0) as u8,
+        ((cb as f64 - 128.0) * 224.0 / 255.0 + 128.0).round().clamp(0.0, 255.0) as u8,
+        ((cr as f64 - 128.0) * 224.0 / 255.0 + 128.0).round().clamp(0.0, 255.0) as u8,
+    )
+}
+
+pub fn recolor(rgb: (u8, u8, u8), tint: (u8, u8, u8)) -> (u8, u8, u8) {
+    (
+        ((rgb.0 as u32 * tint.0 as u32) / 255) as u8,
+        ((rgb.1 as u32 * tint.1 as u32) / 255) as u8,
+        ((rgb.2 as u32 * tint.2 as u32) / 255) as u8,
+    )
+}
+
+pub fn dim(rgb: (u8, u8, u8), factor: f64) -> (u8, u8, u8) {
+    (
+        (rgb.0 as f64 * factor).round().clamp(0.0, 255.0) as u8,
+        (rgb.1 as f64 * factor).round().clamp(0.0, 255.0) as u8,
+        (rgb.2 as f64 * factor).round().clamp(0.0, 255.0) as u8,
+    )
+}
+
+pub fn parse_hex_color(value: &str) -> Option<(u8, u8, u8)> {
+    let value = value.trim_start_matches('#');
+    if value.len() != 6 {
+        return None
+    }
+    let r = u8::from_str_radix(&value[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&value[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&value[4..6], 16).ok()?;
+    Some((r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn tv_to_pc_range_expands_limited_range_midpoints() {
+        assert_eq!(tv_to_pc_range(16, 128, 128), (0, 128, 128));
+        assert_eq!(tv_to_pc_range(235, 128, 128), (255, 128, 128));
+    }
+
+    #[test]
+    fn pc_to_tv_range_narrows_full_range_midpoints() {
+        assert_eq!(pc_to_tv_range(0, 128, 128), (16, 128, 128));
+        assert_eq!(pc_to_tv_range(255, 128, 128), (235, 128, 128));
+    }
+
+    #[test]
+    fn range_conversions_round_trip_through_each_other() {
+        let (y, cb, cr) = pc_to_tv_range(200, 40, 220);
+        assert_eq!(tv_to_pc_range(y, cb, cr), (200, 40, 220));
+    }
+
+    #[test]
+    fn recolor_tints_by_multiplying_each_channel() {
+        assert_eq!(recolor((255, 255, 255), (255, 128, 0)), (255, 128, 0));
+        assert_eq!(recolor((200, 100, 50), (255, 255, 255)), (200, 100, 50));
+    }
+
+    #[test]
+    fn dim_scales_and_clamps_brightness() {
+        assert_eq!(dim((100, 100, 100), 0.5), (50, 50, 50));
+        assert_eq!(dim((200, 200, 200), 2.0), (255, 255, 255));
+    }
+
+    #[test]
+    fn parse_hex_color_accepts_with_and_without_hash() {
+        assert_eq!(parse_hex_color("#ffcc00"), Some((0xff, 0xcc, 0x00)));
+        assert_eq!(parse_hex_color("ffcc00"), Some((0xff, 0xcc, 0x00)));
+    }
+
+    #[test]
+    fn parse_hex_color_rejects_malformed_input() {
+        assert_eq!(parse_hex_color("#fff"), None);
+        assert_eq!(parse_hex_color("#gggggg"), None);
+    }
+}
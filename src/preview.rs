@@ -0,0 +1,165 @@
+/*
+ * Copyright 2020 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::HashMap;
+
+pub struct Placement {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub raster: Vec<u8>,
+}
+
+pub fn composite(
+    width: u16,
+    height: u16,
+    placements: &[Placement],
+    palette: &HashMap<u8, (u8, u8, u8)>,
+) -> Vec<(u8, u8, u8)> {
+
+    let width = width as usize;
+    let height = height as usize;
+    let mut canvas = vec![(0u8, 0u8, 0u8); width * height];
+
+    for placement in placements {
+        for row in 0..placement.height as usize {
+            let canvas_y = placement.y as usize + row;
+            if canvas_y >= height {
+                continue
+            }
+            for col in 0..placement.width as usize {
+                let canvas_x = placement.x as usize + col;
+                if canvas_x >= width {
+                    continue
+                }
+                let index = placement.raster[row * placement.width as usize + col];
+                if let Some(color) = palette.get(&index) {
+                    canvas[canvas_y * width + canvas_x] = *color;
+                }
+            }
+        }
+    }
+
+    canvas
+}
+
+pub fn render_sixel(canvas: &[(u8, u8, u8)], width: u16, height: u16) -> String {
+
+    let width = width as usize;
+    let height = height as usize;
+    let mut palette = Vec::<(u8, u8, u8)>::new();
+    let mut seen = HashMap::<(u8, u8, u8), usize>::new();
+
+    for &color in canvas {
+        if !seen.contains_key(&color) {
+            seen.insert(color, palette.len());
+            palette.push(color);
+        }
+    }
+
+    let mut out = String::new();
+
+    out.push_str("\x1bPq");
+
+    for (index, &(r, g, b)) in palette.iter().enumerate() {
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            index,
+            r as u32 * 100 / 255,
+            g as u32 * 100 / 255,
+            b as u32 * 100 / 255,
+        ));
+    }
+
+    let bands = (height + 5) / 6;
+
+    for band in 0..bands {
+        for (index, &color) in palette.iter().enumerate() {
+            out.push_str(&format!("#{}", index));
+            out.push_str(&sixel_row(canvas, width, height, band * 6, color));
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+
+    out
+}
+
+fn sixel_row(canvas: &[(u8, u8, u8)], width: usize, height: usize, top: usize, color: (u8, u8, u8)) -> String {
+
+    let mut out = String::new();
+    let mut run_char = 0u8;
+    let mut run_len = 0usize;
+
+    for x in 0..width {
+
+        let mut bits = 0u8;
+
+        for bit in 0..6 {
+            let y = top + bit;
+            if y < height && canvas[y * width + x] == color {
+                bits |= 1 << bit;
+            }
+        }
+
+        let ch = bits + 63;
+
+        if ch == run_char {
+            run_len += 1;
+        } else {
+            if run_len > 0 {
+                push_run(&mut out, run_char, run_len);
+            }
+            run_char = ch;
+            run_len = 1;
+        }
+    }
+
+    if run_len > 0 {
+        push_run(&mut out, run_char, run_len);
+    }
+
+    out
+}
+
+fn push_run(out: &mut String, ch: u8, len: usize) {
+    if len > 3 {
+        out.push('!');
+        out.push_str(&len.to_string());
+        out.push(ch as char);
+    } else {
+        for _ in 0..len {
+            out.push(ch as char);
+        }
+    }
+}
+
+pub fn render_ascii(canvas: &[(u8, u8, u8)], width: u16, height: u16) -> String {
+
+    const RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+    let width = width as usize;
+    let height = height as usize;
+    let mut out = String::new();
+    let mut y = 0;
+
+    while y < height {
+        for x in 0..width {
+            let (r, g, b) = canvas[y * width + x];
+            let luma = (0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64) as usize;
+            out.push(RAMP[luma * (RAMP.len() - 1) / 255]);
+        }
+        out.push('\n');
+        y += 2;
+    }
+
+    out
+}
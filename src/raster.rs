@@ -0,0 +1,203 @@
+/*
+ * Copyright 2020 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ */
+
+pub fn decode_rle(data: &[u8], width: u16, height: u16) -> Vec<u8> {
+
+    let width = width as usize;
+    let height = height as usize;
+    let mut raster = vec![0u8; width * height];
+    let mut pos = 0usize;
+    let mut row = 0usize;
+    let mut col = 0usize;
+
+    while pos < data.len() && row < height {
+
+        let b1 = data[pos];
+        pos += 1;
+
+        if b1 != 0 {
+            if col < width {
+                raster[row * width + col] = b1;
+            }
+            col += 1;
+        } else {
+            if pos >= data.len() {
+                break
+            }
+            let b2 = data[pos];
+            pos += 1;
+            if b2 == 0 {
+                row += 1;
+                col = 0;
+                continue
+            }
+            let (len, color) = match b2 >> 6 {
+                0b00 => {
+                    ((b2 & 0x3F) as usize, 0u8)
+                }
+                0b01 => {
+                    if pos >= data.len() {
+                        break
+                    }
+                    let b3 = data[pos];
+                    pos += 1;
+                    ((((b2 & 0x3F) as usize) << 8) | b3 as usize, 0u8)
+                }
+                0b10 => {
+                    if pos >= data.len() {
+                        break
+                    }
+                    let b3 = data[pos];
+                    pos += 1;
+                    ((b2 & 0x3F) as usize, b3)
+                }
+                _ => {
+                    if pos + 1 >= data.len() {
+                        break
+                    }
+                    let b3 = data[pos];
+                    pos += 1;
+                    let b4 = data[pos];
+                    pos += 1;
+                    ((((b2 & 0x3F) as usize) << 8) | b3 as usize, b4)
+                }
+            };
+            for _ in 0..len {
+                if col < width {
+                    raster[row * width + col] = color;
+                }
+                col += 1;
+            }
+        }
+    }
+
+    raster
+}
+
+pub fn encode_rle(raster: &[u8], width: u16, height: u16) -> Vec<u8> {
+
+    let width = width as usize;
+    let height = height as usize;
+    let mut out = Vec::new();
+
+    for row in 0..height {
+
+        let mut col = 0usize;
+
+        while col < width {
+
+            let color = raster[row * width + col];
+            let mut len = 1usize;
+
+            while col + len < width && raster[row * width + col + len] == color {
+                len += 1;
+            }
+
+            if color == 0 {
+                if len <= 0x3F {
+                    out.push(0x00);
+                    out.push(len as u8);
+                } else {
+                    out.push(0x00);
+                    out.push(0x40 | ((len >> 8) as u8 & 0x3F));
+                    out.push((len & 0xFF) as u8);
+                }
+            } else if len == 1 {
+                out.push(color);
+            } else if len <= 0x3F {
+                out.push(0x00);
+                out.push(0x80 | (len as u8 & 0x3F));
+                out.push(color);
+            } else {
+                out.push(0x00);
+                out.push(0xC0 | ((len >> 8) as u8 & 0x3F));
+                out.push((len & 0xFF) as u8);
+                out.push(color);
+            }
+
+            col += len;
+        }
+
+        out.push(0x00);
+        out.push(0x00);
+    }
+
+    out
+}
+
+pub fn resample_nearest(raster: &[u8], src_width: u16, src_height: u16, dst_width: u16, dst_height: u16) -> Vec<u8> {
+
+    let src_width = src_width as usize;
+    let src_height = src_height as usize;
+    let dst_width = dst_width as usize;
+    let dst_height = dst_height as usize;
+    let mut out = vec![0u8; dst_width * dst_height];
+
+    for y in 0..dst_height {
+        let src_y = ((y * src_height) / dst_height.max(1)).min(src_height.saturating_sub(1));
+        for x in 0..dst_width {
+            let src_x = ((x * src_width) / dst_width.max(1)).min(src_width.saturating_sub(1));
+            out[y * dst_width + x] = raster[src_y * src_width + src_x];
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn round_trips_long_runs_and_single_pixels() {
+
+        let width = 100u16;
+        let height = 3u16;
+        let mut raster = vec![0u8; width as usize * height as usize];
+
+        for col in 0..width as usize {
+            raster[width as usize + col] = 7;
+        }
+        for col in 0..width as usize {
+            raster[2 * width as usize + col] = (col % 5) as u8 + 1;
+        }
+
+        let encoded = encode_rle(&raster, width, height);
+        let decoded = decode_rle(&encoded, width, height);
+
+        assert_eq!(decoded, raster);
+    }
+
+    #[test]
+    fn round_trips_short_runs() {
+
+        let width = 8u16;
+        let height = 2u16;
+        let raster = vec![0, 1, 1, 0, 2, 2, 2, 3, 4, 4, 0, 0, 0, 5, 6, 6];
+
+        let encoded = encode_rle(&raster, width, height);
+        let decoded = decode_rle(&encoded, width, height);
+
+        assert_eq!(decoded, raster);
+    }
+
+    #[test]
+    fn truncated_multi_byte_run_header_does_not_panic() {
+        let data = vec![0x00, 0xC0];
+        let raster = decode_rle(&data, 4, 4);
+        assert_eq!(raster.len(), 16);
+    }
+
+    #[test]
+    fn truncated_fourteen_bit_run_header_does_not_panic() {
+        let data = vec![0x00, 0x40];
+        let raster = decode_rle(&data, 4, 4);
+        assert_eq!(raster.len(), 16);
+    }
+}